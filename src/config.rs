@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Top-level `md2pdf.toml` schema. Every field is optional so a config file
+/// only needs to mention what it wants to override.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub theme: ThemeConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ThemeConfig {
+    /// Name of a built-in syntect theme, e.g. "base16-ocean.dark".
+    pub syntax_theme: Option<String>,
+    /// Path to a user-supplied `.tmTheme` file, takes priority over `syntax_theme`.
+    pub syntax_theme_file: Option<PathBuf>,
+    /// Path to a CSS file to layer on top of (or instead of) the built-in stylesheet.
+    pub stylesheet: Option<PathBuf>,
+    /// When true, `stylesheet` replaces the built-in CSS instead of being appended after it.
+    #[serde(default)]
+    pub replace_stylesheet: bool,
+    pub body_font: Option<String>,
+    pub heading_font: Option<String>,
+    pub code_font: Option<String>,
+    /// Page background color, e.g. "#fdfcfb".
+    pub background: Option<String>,
+    /// Container padding, e.g. "60px 40px".
+    pub margin: Option<String>,
+}
+
+impl Config {
+    /// Loads a config file if it exists, falling back to defaults when it doesn't.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Error reading config file: {:?}", path))?;
+        toml::from_str(&raw).with_context(|| format!("Error parsing config file: {:?}", path))
+    }
+}
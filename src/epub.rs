@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// A single spine entry, produced by splitting the converted document on
+/// each top-level `<h1>`.
+struct Chapter {
+    id: String,
+    title: String,
+    /// Inner XHTML body for this chapter (no `<html>`/`<body>` wrapper).
+    body: String,
+    /// True for the synthetic chapter holding content before the first
+    /// `<h1>` (e.g. an introduction paragraph) — never a real title.
+    is_front_matter: bool,
+}
+
+/// Builds an EPUB 3 container from the HTML produced by `markdown_to_html`,
+/// splitting the document into one chapter per top-level `<h1>` and reusing
+/// `css` (the theme's stylesheet) for all of them.
+pub fn generate_epub(html: &str, css: &str, output_path: &Path) -> Result<()> {
+    let body = extract_container_body(html);
+    let body = strip_in_document_toc(body);
+    let chapters = split_into_chapters(&body);
+
+    let file = std::fs::File::create(output_path)
+        .with_context(|| format!("Error creating EPUB file: {:?}", output_path))?;
+    let mut zip = ZipWriter::new(file);
+
+    // The mimetype entry must be the first one in the archive and stored
+    // uncompressed, per the EPUB OCF spec.
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(container_xml().as_bytes())?;
+
+    zip.start_file("OEBPS/styles.css", deflated)?;
+    zip.write_all(css.as_bytes())?;
+
+    for chapter in &chapters {
+        zip.start_file(format!("OEBPS/{}.xhtml", chapter.id), deflated)?;
+        zip.write_all(chapter_xhtml(chapter).as_bytes())?;
+    }
+
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(content_opf(&chapters).as_bytes())?;
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav_xhtml(&chapters).as_bytes())?;
+
+    zip.start_file("OEBPS/toc.ncx", deflated)?;
+    zip.write_all(toc_ncx(&chapters).as_bytes())?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Pulls the inner HTML out of `<body><div class="container">...</div></body>`,
+/// which is the fixed shape `markdown_to_html` always emits.
+fn extract_container_body(html: &str) -> &str {
+    let start_marker = "<div class=\"container\">";
+    let start = html
+        .find(start_marker)
+        .map(|i| i + start_marker.len())
+        .unwrap_or(0);
+    let end = html.rfind("</div></body>").unwrap_or(html.len());
+    &html[start..end.max(start)]
+}
+
+/// Drops a `--toc`-generated `<nav class="toc">...</nav>` block from the
+/// body, if present. Its `href="#slug"` anchors only work same-page, but
+/// `split_into_chapters` moves each `<h1>` chapter into its own XHTML file, so the
+/// in-document TOC would ship as dead links; the EPUB gets its own
+/// navigation for free via `nav.xhtml`/`toc.ncx`.
+fn strip_in_document_toc(body: &str) -> String {
+    let start_marker = "<nav class=\"toc\">";
+    let end_marker = "</nav>";
+    match body.find(start_marker) {
+        Some(start) => match body[start..].find(end_marker) {
+            Some(end_rel) => {
+                let end = start + end_rel + end_marker.len();
+                format!("{}{}", &body[..start], &body[end..])
+            }
+            None => body.to_string(),
+        },
+        None => body.to_string(),
+    }
+}
+
+/// Splits the document body on each top-level `<h1>`, so every chapter in
+/// the EPUB spine starts at a level-1 heading.
+fn split_into_chapters(body: &str) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    let mut rest = body;
+
+    // Anything before the first <h1> becomes an untitled front-matter chapter.
+    if let Some(first_h1) = rest.find("<h1") {
+        if first_h1 > 0 {
+            chapters.push(Chapter {
+                id: "chapter-0".to_string(),
+                title: "Front Matter".to_string(),
+                body: rest[..first_h1].to_string(),
+                is_front_matter: true,
+            });
+        }
+        rest = &rest[first_h1..];
+    } else {
+        chapters.push(Chapter {
+            id: "chapter-0".to_string(),
+            title: "Document".to_string(),
+            body: rest.to_string(),
+            is_front_matter: false,
+        });
+        return chapters;
+    }
+
+    let mut index = chapters.len();
+    while !rest.is_empty() {
+        let next_h1 = rest[3..].find("<h1").map(|i| i + 3);
+        let (chunk, remainder) = match next_h1 {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, ""),
+        };
+        chapters.push(Chapter {
+            id: format!("chapter-{}", index),
+            title: heading_text(chunk).unwrap_or_else(|| format!("Chapter {}", index)),
+            body: chunk.to_string(),
+            is_front_matter: false,
+        });
+        index += 1;
+        rest = remainder;
+    }
+
+    chapters
+}
+
+/// Picks the book's title from the first real chapter, skipping the
+/// synthetic front-matter chapter when there is one.
+fn document_title(chapters: &[Chapter]) -> &str {
+    chapters
+        .iter()
+        .find(|c| !c.is_front_matter)
+        .or_else(|| chapters.first())
+        .map(|c| c.title.as_str())
+        .unwrap_or("Untitled")
+}
+
+/// Strips tags from the first `<h1>...</h1>` in `html` to get a plain-text title.
+fn heading_text(html: &str) -> Option<String> {
+    let start = html.find("<h1")?;
+    let open_end = html[start..].find('>')? + start + 1;
+    let close = html[open_end..].find("</h1>")? + open_end;
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html[open_end..close].chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+fn chapter_xhtml(chapter: &Chapter) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+    <meta charset="UTF-8" />
+    <title>{title}</title>
+    <link rel="stylesheet" type="text/css" href="styles.css" />
+</head>
+<body>
+<div class="container">
+{body}
+</div>
+</body>
+</html>
+"#,
+        title = chapter.title,
+        body = chapter.body
+    )
+}
+
+fn container_xml() -> String {
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+    <rootfiles>
+        <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+    </rootfiles>
+</container>
+"#
+    .to_string()
+}
+
+fn content_opf(chapters: &[Chapter]) -> String {
+    let manifest_items: String = chapters
+        .iter()
+        .map(|c| {
+            format!(
+                "        <item id=\"{id}\" href=\"{id}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+                id = c.id
+            )
+        })
+        .collect();
+    let spine_items: String = chapters
+        .iter()
+        .map(|c| format!("        <itemref idref=\"{}\"/>\n", c.id))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+    <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+        <dc:identifier id="book-id">urn:uuid:md2pdf-epub</dc:identifier>
+        <dc:title>{title}</dc:title>
+        <dc:language>en</dc:language>
+        <meta property="dcterms:modified">2024-01-01T00:00:00Z</meta>
+    </metadata>
+    <manifest>
+        <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+        <item id="css" href="styles.css" media-type="text/css"/>
+        <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+{manifest_items}    </manifest>
+    <spine toc="ncx">
+{spine_items}    </spine>
+</package>
+"#,
+        title = document_title(chapters),
+        manifest_items = manifest_items,
+        spine_items = spine_items,
+    )
+}
+
+fn nav_xhtml(chapters: &[Chapter]) -> String {
+    let entries: String = chapters
+        .iter()
+        .map(|c| format!("            <li><a href=\"{id}.xhtml\">{title}</a></li>\n", id = c.id, title = c.title))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><meta charset="UTF-8" /><title>Table of Contents</title></head>
+<body>
+    <nav epub:type="toc">
+        <ol>
+{entries}        </ol>
+    </nav>
+</body>
+</html>
+"#,
+        entries = entries
+    )
+}
+
+fn toc_ncx(chapters: &[Chapter]) -> String {
+    let nav_points: String = chapters
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            format!(
+                r#"        <navPoint id="navpoint-{n}" playOrder="{order}">
+            <navLabel><text>{title}</text></navLabel>
+            <content src="{id}.xhtml"/>
+        </navPoint>
+"#,
+                n = i,
+                order = i + 1,
+                title = c.title,
+                id = c.id
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+    <head>
+        <meta name="dtb:uid" content="urn:uuid:md2pdf-epub"/>
+    </head>
+    <docTitle><text>{title}</text></docTitle>
+    <navMap>
+{nav_points}    </navMap>
+</ncx>
+"#,
+        title = document_title(chapters),
+        nav_points = nav_points
+    )
+}
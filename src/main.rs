@@ -1,12 +1,17 @@
+mod config;
+mod epub;
+
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use config::{Config, ThemeConfig};
 use headless_chrome::{Browser, LaunchOptions};
 use pulldown_cmark::{Event, Options, Parser as MdParser, Tag};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, ThemeSet};
+use syntect::highlighting::{Style, Theme, ThemeSet};
 use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
@@ -25,15 +30,112 @@ struct Args {
     /// Page margin in pixels (default: 50)
     #[arg(short, long, default_value = "50")]
     margin: u32,
+
+    /// Syntect theme name to highlight code with (overrides md2pdf.toml)
+    #[arg(long)]
+    theme: Option<String>,
+
+    /// Path to a md2pdf.toml config file controlling theme, fonts and CSS
+    #[arg(long, default_value = "md2pdf.toml")]
+    config: PathBuf,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "pdf")]
+    format: OutputFormat,
+
+    /// Render as a slide deck instead of a continuous document
+    #[arg(long)]
+    slides: bool,
+
+    /// What splits one slide from the next in --slides mode
+    #[arg(long, value_enum, default_value = "rule")]
+    slide_break: SlideBreak,
+
+    /// Insert a table of contents linking to each heading
+    #[arg(long)]
+    toc: bool,
+
+    /// Paper size for PDF output
+    #[arg(long, value_enum, default_value = "a4")]
+    paper: PaperSize,
+
+    /// Force landscape orientation (--slides already implies this)
+    #[arg(long)]
+    landscape: bool,
+
+    /// HTML template for the running header, e.g. '<span class="title"></span>'
+    #[arg(long)]
+    header: Option<String>,
+
+    /// HTML template for the running footer, e.g. '<span class="pageNumber"></span> / <span class="totalPages"></span>'
+    #[arg(long)]
+    footer: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    Pdf,
+    Epub,
+    Html,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum SlideBreak {
+    /// Start a new slide at each thematic break (`---`)
+    Rule,
+    /// Start a new slide at each top-level heading (H1 or H2)
+    Heading,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum PaperSize {
+    A4,
+    Letter,
+    Legal,
+}
+
+impl PaperSize {
+    /// Paper dimensions in inches, as `print_to_pdf` expects.
+    fn dimensions_in(self) -> (f64, f64) {
+        match self {
+            PaperSize::A4 => (8.27, 11.69),
+            PaperSize::Letter => (8.5, 11.0),
+            PaperSize::Legal => (8.5, 14.0),
+        }
+    }
+}
+
+/// Page layout options for `generate_pdf`, gathered in one place since they
+/// all end up on the same `PrintToPdfOptions` call.
+struct PdfLayout {
+    margin_px: u32,
+    paper: PaperSize,
+    landscape: bool,
+    header_template: Option<String>,
+    footer_template: Option<String>,
+    slides: bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    if args.slides && matches!(args.format, OutputFormat::Epub) {
+        anyhow::bail!("--slides is not supported with --format epub: the slide deck layout has no notion of chapters");
+    }
+
+    if args.slides && args.toc {
+        anyhow::bail!("--toc is not supported with --slides: a slide deck has no table-of-contents page to render it into");
+    }
+
     // Define output file
     let output_path = args.output.unwrap_or_else(|| {
         let mut path = args.input.clone();
-        path.set_extension("pdf");
+        let extension = match args.format {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Epub => "epub",
+            OutputFormat::Html => "html",
+        };
+        path.set_extension(extension);
         path
     });
 
@@ -41,17 +143,51 @@ fn main() -> Result<()> {
     let markdown_content = fs::read_to_string(&args.input)
         .with_context(|| format!("Error reading file: {:?}", args.input))?;
 
+    // Load the theme/config file, if any, and let --theme override its syntax theme
+    let config = Config::load_or_default(&args.config)?;
+
     // Convert Markdown to HTML
-    let html_content = markdown_to_html(&markdown_content)?;
+    let html_content = if args.slides {
+        markdown_to_slides(
+            &markdown_content,
+            &config.theme,
+            args.theme.as_deref(),
+            args.slide_break,
+        )?
+    } else {
+        markdown_to_html(&markdown_content, &config.theme, args.theme.as_deref(), args.toc)?
+    };
 
-    // Generate the PDF
-    generate_pdf(&html_content, &output_path, args.margin)?;
+    match args.format {
+        OutputFormat::Pdf => {
+            let layout = PdfLayout {
+                margin_px: args.margin,
+                paper: args.paper,
+                landscape: args.landscape,
+                header_template: args.header.clone(),
+                footer_template: args.footer.clone(),
+                slides: args.slides,
+            };
+            generate_pdf(&html_content, &output_path, layout)?
+        }
+        OutputFormat::Epub => {
+            let css = resolve_css(&config.theme)?;
+            epub::generate_epub(&html_content, &css, &output_path)?;
+        }
+        OutputFormat::Html => fs::write(&output_path, &html_content)
+            .with_context(|| format!("Error writing HTML file: {:?}", output_path))?,
+    }
 
-    println!("✅ PDF generated successfully: {:?}", output_path);
+    println!("✅ {:?} generated successfully: {:?}", args.format, output_path);
     Ok(())
 }
 
-fn markdown_to_html(markdown: &str) -> Result<String> {
+fn markdown_to_html(
+    markdown: &str,
+    theme_config: &ThemeConfig,
+    cli_theme: Option<&str>,
+    include_toc: bool,
+) -> Result<String> {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TABLES);
@@ -60,20 +196,26 @@ fn markdown_to_html(markdown: &str) -> Result<String> {
 
     let parser = MdParser::new_ext(markdown, options);
     let mut html_output = String::new();
-    
+
     // Syntect configuration
     let ps = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-    // Use a darker theme for better contrast in PDF
-    let theme = &ts.themes["base16-ocean.dark"];
-    
+    let theme = resolve_theme(theme_config, cli_theme)?;
+
+    // Headings get stable slug ids regardless of --toc; collected up front
+    // so the id assigned to each <hN> during the walk below matches the toc.
+    let headings = collect_headings(markdown);
+    let mut heading_index = 0usize;
+
     let mut code_block = String::new();
     let mut in_code_block = false;
     let mut code_lang = String::new();
 
     // Add CSS and HTML structure
-    html_output.push_str(&get_html_template());
+    html_output.push_str(&get_html_template(theme_config)?);
     html_output.push_str("<body><div class=\"container\">");
+    if include_toc {
+        html_output.push_str(&render_toc(&headings));
+    }
 
     for event in parser {
         match event {
@@ -87,11 +229,7 @@ fn markdown_to_html(markdown: &str) -> Result<String> {
             }
             Event::End(Tag::CodeBlock(_)) => {
                 in_code_block = false;
-                let highlighted = highlight_code(&code_block, &code_lang, &ps, theme)?;
-                html_output.push_str(&format!(
-                    "<div class=\"code-block\"><pre><code>{}</code></pre></div>",
-                    highlighted
-                ));
+                html_output.push_str(&render_code_block(&code_block, &code_lang, &ps, &theme)?);
             }
             Event::Text(text) => {
                 if in_code_block {
@@ -104,26 +242,15 @@ fn markdown_to_html(markdown: &str) -> Result<String> {
                 html_output.push_str(&format!("<code class=\"inline-code\">{}</code>", escape_html(&text)));
             }
             Event::Start(Tag::Heading(level, _, _)) => {
-                let level_num = match level {
-                    pulldown_cmark::HeadingLevel::H1 => 1,
-                    pulldown_cmark::HeadingLevel::H2 => 2,
-                    pulldown_cmark::HeadingLevel::H3 => 3,
-                    pulldown_cmark::HeadingLevel::H4 => 4,
-                    pulldown_cmark::HeadingLevel::H5 => 5,
-                    pulldown_cmark::HeadingLevel::H6 => 6,
-                };
-                html_output.push_str(&format!("<h{}>", level_num));
+                let slug = headings
+                    .get(heading_index)
+                    .map(|(_, _, slug)| slug.as_str())
+                    .unwrap_or("");
+                html_output.push_str(&format!("<h{} id=\"{}\">", heading_level_num(level), slug));
             }
             Event::End(Tag::Heading(level, _, _)) => {
-                let level_num = match level {
-                    pulldown_cmark::HeadingLevel::H1 => 1,
-                    pulldown_cmark::HeadingLevel::H2 => 2,
-                    pulldown_cmark::HeadingLevel::H3 => 3,
-                    pulldown_cmark::HeadingLevel::H4 => 4,
-                    pulldown_cmark::HeadingLevel::H5 => 5,
-                    pulldown_cmark::HeadingLevel::H6 => 6,
-                };
-                html_output.push_str(&format!("</h{}>", level_num));
+                html_output.push_str(&format!("</h{}>", heading_level_num(level)));
+                heading_index += 1;
             }
             Event::Start(Tag::Paragraph) => html_output.push_str("<p>"),
             Event::End(Tag::Paragraph) => html_output.push_str("</p>"),
@@ -173,6 +300,309 @@ fn markdown_to_html(markdown: &str) -> Result<String> {
     Ok(html_output)
 }
 
+/// Like `markdown_to_html`, but walks the event stream into a deck of
+/// `<section>`s instead of one continuous `.container`, splitting on
+/// `Event::Rule` or on top-level headings depending on `slide_break`.
+fn markdown_to_slides(
+    markdown: &str,
+    theme_config: &ThemeConfig,
+    cli_theme: Option<&str>,
+    slide_break: SlideBreak,
+) -> Result<String> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let parser = MdParser::new_ext(markdown, options);
+    let ps = SyntaxSet::load_defaults_newlines();
+    let theme = resolve_theme(theme_config, cli_theme)?;
+
+    let mut slides: Vec<String> = vec![String::new()];
+    let mut code_block = String::new();
+    let mut in_code_block = false;
+    let mut code_lang = String::new();
+
+    for event in parser {
+        let starts_new_slide = match (&slide_break, &event) {
+            (SlideBreak::Rule, Event::Rule) => true,
+            (
+                SlideBreak::Heading,
+                Event::Start(Tag::Heading(
+                    pulldown_cmark::HeadingLevel::H1 | pulldown_cmark::HeadingLevel::H2,
+                    ..,
+                )),
+            ) => true,
+            _ => false,
+        };
+        if starts_new_slide && !slides.last().unwrap().is_empty() {
+            slides.push(String::new());
+        }
+        let slide = slides.last_mut().unwrap();
+
+        match event {
+            Event::Rule => {} // consumed as a slide break, not rendered
+            Event::Start(Tag::CodeBlock(kind)) => {
+                in_code_block = true;
+                code_block.clear();
+                code_lang = match kind {
+                    pulldown_cmark::CodeBlockKind::Fenced(lang) => lang.to_string(),
+                    _ => String::new(),
+                };
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                in_code_block = false;
+                slide.push_str(&render_code_block(&code_block, &code_lang, &ps, &theme)?);
+            }
+            Event::Text(text) => {
+                if in_code_block {
+                    code_block.push_str(&text);
+                } else {
+                    slide.push_str(&escape_html(&text));
+                }
+            }
+            Event::Code(text) => {
+                slide.push_str(&format!("<code class=\"inline-code\">{}</code>", escape_html(&text)));
+            }
+            Event::Start(Tag::Heading(level, _, _)) => {
+                slide.push_str(&format!("<h{}>", heading_level_num(level)));
+            }
+            Event::End(Tag::Heading(level, _, _)) => {
+                slide.push_str(&format!("</h{}>", heading_level_num(level)));
+            }
+            Event::Start(Tag::Paragraph) => slide.push_str("<p>"),
+            Event::End(Tag::Paragraph) => slide.push_str("</p>"),
+            Event::Start(Tag::List(None)) => slide.push_str("<ul>"),
+            Event::End(Tag::List(None)) => slide.push_str("</ul>"),
+            Event::Start(Tag::List(Some(_))) => slide.push_str("<ol>"),
+            Event::End(Tag::List(Some(_))) => slide.push_str("</ol>"),
+            Event::Start(Tag::Item) => slide.push_str("<li>"),
+            Event::End(Tag::Item) => slide.push_str("</li>"),
+            Event::Start(Tag::BlockQuote) => slide.push_str("<blockquote>"),
+            Event::End(Tag::BlockQuote) => slide.push_str("</blockquote>"),
+            Event::Start(Tag::Emphasis) => slide.push_str("<em>"),
+            Event::End(Tag::Emphasis) => slide.push_str("</em>"),
+            Event::Start(Tag::Strong) => slide.push_str("<strong>"),
+            Event::End(Tag::Strong) => slide.push_str("</strong>"),
+            Event::Start(Tag::Link(_, url, title)) => {
+                slide.push_str(&format!(
+                    "<a href=\"{}\" title=\"{}\">",
+                    url,
+                    escape_html(&title)
+                ));
+            }
+            Event::End(Tag::Link(_, _, _)) => slide.push_str("</a>"),
+            Event::Start(Tag::Image(_, url, title)) => {
+                slide.push_str(&format!(
+                    "<img src=\"{}\" alt=\"{}\" />",
+                    url,
+                    escape_html(&title)
+                ));
+            }
+            Event::Start(Tag::Table(_)) => slide.push_str("<table>"),
+            Event::End(Tag::Table(_)) => slide.push_str("</table>"),
+            Event::Start(Tag::TableHead) => slide.push_str("<thead>"),
+            Event::End(Tag::TableHead) => slide.push_str("</thead>"),
+            Event::Start(Tag::TableRow) => slide.push_str("<tr>"),
+            Event::End(Tag::TableRow) => slide.push_str("</tr>"),
+            Event::Start(Tag::TableCell) => slide.push_str("<td>"),
+            Event::End(Tag::TableCell) => slide.push_str("</td>"),
+            Event::HardBreak => slide.push_str("<br />"),
+            Event::SoftBreak => slide.push_str(" "),
+            _ => {}
+        }
+    }
+
+    let mut html_output = get_html_template(theme_config)?;
+    html_output.push_str(
+        r#"<style>
+        .reveal .slides { width: 100%; }
+        .slides > section {
+            min-height: 100vh;
+            padding: 60px 40px;
+            page-break-after: always;
+            break-after: page;
+            display: flex;
+            flex-direction: column;
+            justify-content: center;
+        }
+        .slides > section:last-child {
+            page-break-after: auto;
+            break-after: auto;
+        }
+    </style>"#,
+    );
+    html_output.push_str("<body><div class=\"reveal\"><div class=\"slides\">");
+    for slide in slides.into_iter().filter(|s| !s.trim().is_empty()) {
+        html_output.push_str("<section>");
+        html_output.push_str(&slide);
+        html_output.push_str("</section>");
+    }
+    html_output.push_str("</div></div></body></html>");
+    Ok(html_output)
+}
+
+fn heading_level_num(level: pulldown_cmark::HeadingLevel) -> u8 {
+    match level {
+        pulldown_cmark::HeadingLevel::H1 => 1,
+        pulldown_cmark::HeadingLevel::H2 => 2,
+        pulldown_cmark::HeadingLevel::H3 => 3,
+        pulldown_cmark::HeadingLevel::H4 => 4,
+        pulldown_cmark::HeadingLevel::H5 => 5,
+        pulldown_cmark::HeadingLevel::H6 => 6,
+    }
+}
+
+/// First pass over the document: buffers the text of every heading and
+/// assigns each a GitHub-style slug, in document order.
+fn collect_headings(markdown: &str) -> Vec<(u8, String, String)> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let parser = MdParser::new_ext(markdown, options);
+    let mut headings = Vec::new();
+    let mut seen = HashMap::new();
+    let mut current: Option<(u8, String)> = None;
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) => {
+                current = Some((heading_level_num(level), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, buf)) = current.as_mut() {
+                    buf.push_str(&text);
+                }
+            }
+            Event::End(Tag::Heading(_, _, _)) => {
+                if let Some((level, text)) = current.take() {
+                    let slug = slugify(&text, &mut seen);
+                    headings.push((level, text, slug));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    headings
+}
+
+/// GitHub-style slugify: lowercase, strip everything but alphanumerics,
+/// spaces and hyphens, collapse whitespace runs to a single hyphen, and
+/// de-duplicate repeats by appending `-1`, `-2`, ...
+fn slugify(text: &str, seen: &mut HashMap<String, u32>) -> String {
+    let mut slug = String::new();
+    let mut pending_hyphen = false;
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() || c == '-' {
+            if pending_hyphen && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_hyphen = false;
+            slug.push(c);
+        } else if c.is_whitespace() {
+            pending_hyphen = true;
+        }
+        // Other punctuation is dropped entirely.
+    }
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+
+    let count = seen.entry(slug.clone()).or_insert(0);
+    if *count == 0 {
+        *count += 1;
+        slug
+    } else {
+        let deduped = format!("{}-{}", slug, count);
+        *count += 1;
+        deduped
+    }
+}
+
+/// Renders a nested `<ul>` table of contents from `(level, text, slug)`
+/// tuples collected by `collect_headings`.
+fn render_toc(headings: &[(u8, String, String)]) -> String {
+    if headings.is_empty() {
+        return String::new();
+    }
+
+    // `levels` is a stack of the levels of every `<li>` that is currently
+    // open, from the root down to the most recently opened heading. Going
+    // deeper opens a new nested `<ul>` *inside* the still-open parent `<li>`
+    // instead of closing it first; coming back up closes each `<li>`/`<ul>`
+    // pair in turn before deciding whether the new heading is a sibling of
+    // what's left on the stack or starts a fresh nested list under it. This
+    // is what keeps multi-level skips (e.g. an H1 followed directly by an
+    // H3) balanced instead of emitting a stray closing tag for a level that
+    // never had an opening `<li>`.
+    let mut levels: Vec<u8> = Vec::new();
+    let mut toc = String::from("<nav class=\"toc\">\n<ul>\n");
+
+    for (level, text, slug) in headings {
+        match levels.last().copied() {
+            None => levels.push(*level),
+            Some(top) if *level > top => {
+                toc.push_str("<ul>\n");
+                levels.push(*level);
+            }
+            Some(mut top) => {
+                while levels.len() > 1 && top > *level {
+                    toc.push_str("</li>\n</ul>\n");
+                    levels.pop();
+                    top = *levels.last().unwrap();
+                }
+                if top > *level {
+                    // Shallower than even the very first heading: nothing
+                    // left to close, just lower the floor.
+                    toc.push_str("</li>\n");
+                    *levels.last_mut().unwrap() = *level;
+                } else if top == *level {
+                    toc.push_str("</li>\n");
+                } else {
+                    toc.push_str("<ul>\n");
+                    levels.push(*level);
+                }
+            }
+        }
+        toc.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            slug,
+            escape_html(text)
+        ));
+    }
+
+    toc.push_str("</li>\n");
+    while levels.len() > 1 {
+        toc.push_str("</ul>\n</li>\n");
+        levels.pop();
+    }
+    toc.push_str("</ul>\n</nav>\n");
+    toc
+}
+
+/// Renders a fenced code block, routing diagram/math languages to the
+/// renderer placeholders that `RENDERER_SCRIPTS` brings to life in-browser
+/// instead of syntax-highlighting them as code.
+fn render_code_block(code: &str, lang: &str, ps: &SyntaxSet, theme: &syntect::highlighting::Theme) -> Result<String> {
+    match lang {
+        "mermaid" => Ok(format!("<div class=\"mermaid\">{}</div>", escape_html(code))),
+        "dot" | "graphviz" => Ok(format!("<div class=\"graphviz\">{}</div>", escape_html(code))),
+        "math" => Ok(format!("<div class=\"math-display\">{}</div>", escape_html(code))),
+        _ => {
+            let highlighted = highlight_code(code, lang, ps, theme)?;
+            Ok(format!(
+                "<div class=\"code-block\"><pre><code>{}</code></pre></div>",
+                highlighted
+            ))
+        }
+    }
+}
+
 fn highlight_code(code: &str, lang: &str, ps: &SyntaxSet, theme: &syntect::highlighting::Theme) -> Result<String> {
     let syntax = ps.find_syntax_by_token(lang)
         .or_else(|| ps.find_syntax_by_extension(lang))
@@ -192,6 +622,34 @@ fn highlight_code(code: &str, lang: &str, ps: &SyntaxSet, theme: &syntect::highl
     Ok(highlighted)
 }
 
+/// Resolves the syntect theme to highlight code with, in priority order:
+/// `--theme` flag, then `syntax_theme_file`, then `syntax_theme` from the
+/// config file, falling back to the previous hardcoded default.
+fn resolve_theme(theme_config: &ThemeConfig, cli_theme: Option<&str>) -> Result<Theme> {
+    let ts = ThemeSet::load_defaults();
+    if let Some(name) = cli_theme {
+        return ts
+            .themes
+            .get(name)
+            .cloned()
+            .with_context(|| format!("Unknown syntect theme: {:?}", name));
+    }
+
+    if let Some(path) = &theme_config.syntax_theme_file {
+        return ThemeSet::get_theme(path)
+            .with_context(|| format!("Error loading theme file: {:?}", path));
+    }
+
+    let name = theme_config
+        .syntax_theme
+        .as_deref()
+        .unwrap_or("base16-ocean.dark");
+    ts.themes
+        .get(name)
+        .cloned()
+        .with_context(|| format!("Unknown syntect theme: {:?}", name))
+}
+
 fn escape_html(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -200,44 +658,70 @@ fn escape_html(text: &str) -> String {
         .replace('\'', "&#39;")
 }
 
-fn get_html_template() -> String {
-    r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <style>
+/// Builds the document CSS from the theme config: the built-in stylesheet
+/// (fonts/background/margin substituted in), with an optional external
+/// stylesheet appended after it or replacing it entirely.
+fn resolve_css(theme_config: &ThemeConfig) -> Result<String> {
+    let body_font = theme_config
+        .body_font
+        .as_deref()
+        .unwrap_or("'Crimson Text', serif");
+    let heading_font = theme_config
+        .heading_font
+        .as_deref()
+        .unwrap_or("'Inter', sans-serif");
+    let code_font = theme_config
+        .code_font
+        .as_deref()
+        .unwrap_or("'Fira Code', 'Consolas', 'Monaco', monospace");
+    let background = theme_config.background.as_deref().unwrap_or("#fdfcfb");
+    let margin = theme_config.margin.as_deref().unwrap_or("60px 40px");
+
+    let built_in_css = format!(
+        r#"
         @import url('https://fonts.googleapis.com/css2?family=Crimson+Text:ital,wght@0,400;0,600;0,700;1,400&family=Inter:wght@400;500;600;700&family=Fira+Code:wght@300;400;500&display=swap');
-        
-        * {
+
+        :root {{
+            --body-font: {body_font};
+            --heading-font: {heading_font};
+            --code-font: {code_font};
+            --page-background: {background};
+            --page-margin: {margin};
+        }}
+
+        * {{
             margin: 0;
             padding: 0;
             box-sizing: border-box;
-        }
-        
-        body {
-            font-family: 'Crimson Text', serif;
+        }}
+
+        body {{
+            font-family: var(--body-font);
             line-height: 1.8;
             color: #2c3e50;
-            background-color: #fdfcfb;
+            background-color: var(--page-background);
             font-size: 18px;
-        }
-        
-        .container {
+        }}
+
+        .container {{
             max-width: 800px;
             margin: 0 auto;
-            padding: 60px 40px;
-        }
-        
-        h1, h2, h3, h4, h5, h6 {
-            font-family: 'Inter', sans-serif;
+            padding: var(--page-margin);
+        }}
+
+        h1, h2, h3, h4, h5, h6 {{
+            font-family: var(--heading-font);
             color: #1a202c;
             margin-top: 2.5em;
             margin-bottom: 0.8em;
             font-weight: 700;
             line-height: 1.3;
-        }
-        
+        }}
+        "#
+    );
+
+    let built_in_css = built_in_css
+        + r#"
         h1 {
             font-size: 2.5em;
             border-bottom: 3px solid #e74c3c;
@@ -273,7 +757,7 @@ fn get_html_template() -> String {
         }
         
         code.inline-code {
-            font-family: 'Fira Code', 'Consolas', 'Monaco', monospace;
+            font-family: var(--code-font);
             background-color: #2b303b;
             color: #bf616a;
             padding: 0.2em 0.4em;
@@ -294,7 +778,7 @@ fn get_html_template() -> String {
         
         .code-block pre {
             margin: 0;
-            font-family: 'Fira Code', 'Consolas', 'Monaco', monospace;
+            font-family: var(--code-font);
             font-size: 0.85em;
             line-height: 1.5;
         }
@@ -305,7 +789,13 @@ fn get_html_template() -> String {
             padding: 0;
             font-family: inherit;
         }
-        
+
+        .math-display {
+            margin: 1.5em 0;
+            overflow-x: auto;
+            text-align: center;
+        }
+
         blockquote {
             border-left: 4px solid #e74c3c;
             padding-left: 1.5em;
@@ -325,7 +815,26 @@ fn get_html_template() -> String {
         li {
             margin-bottom: 0.5em;
         }
-        
+
+        .toc {
+            margin-bottom: 2em;
+            padding-bottom: 1.5em;
+            border-bottom: 2px solid #ecf0f1;
+        }
+
+        .toc ul {
+            list-style: none;
+            padding-left: 1.2em;
+        }
+
+        .toc > ul {
+            padding-left: 0;
+        }
+
+        .toc a {
+            color: #2c3e50;
+        }
+
         hr {
             border: none;
             border-top: 2px solid #ecf0f1;
@@ -417,17 +926,134 @@ fn get_html_template() -> String {
                 color-adjust: exact;
             }
         }
+        "#;
+
+    match &theme_config.stylesheet {
+        Some(path) => {
+            let custom_css = fs::read_to_string(path)
+                .with_context(|| format!("Error reading stylesheet: {:?}", path))?;
+            if theme_config.replace_stylesheet {
+                Ok(custom_css)
+            } else {
+                Ok(format!("{}\n{}", built_in_css, custom_css))
+            }
+        }
+        None => Ok(built_in_css),
+    }
+}
+
+/// CDN renderers for fenced diagram/math blocks (mermaid, graphviz, KaTeX),
+/// plus a small bootstrap script that renders them on load and tracks
+/// completion in `window.__md2pdfPending` so `generate_pdf` knows when the
+/// page is actually ready to be rasterized.
+const RENDERER_SCRIPTS: &str = r#"
+    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16/dist/katex.min.css">
+    <script src="https://cdn.jsdelivr.net/npm/mermaid@10/dist/mermaid.min.js"></script>
+    <script src="https://cdn.jsdelivr.net/npm/viz.js@2/viz.js"></script>
+    <script src="https://cdn.jsdelivr.net/npm/viz.js@2/full.render.js"></script>
+    <script src="https://cdn.jsdelivr.net/npm/katex@0.16/dist/katex.min.js"></script>
+    <script src="https://cdn.jsdelivr.net/npm/katex@0.16/dist/contrib/auto-render.min.js"></script>
+    <script>
+        window.__md2pdfPending = 0;
+        document.addEventListener('DOMContentLoaded', function () {
+            var mermaidEls = document.querySelectorAll('.mermaid');
+            var graphvizEls = document.querySelectorAll('.graphviz');
+            var mathEls = document.querySelectorAll('.math-display');
+            window.__md2pdfPending = mermaidEls.length + graphvizEls.length + mathEls.length;
+
+            if (window.mermaid) {
+                mermaid.initialize({ startOnLoad: false });
+                mermaidEls.forEach(function (el, i) {
+                    mermaid.render('md2pdf-mermaid-' + i, el.textContent).then(function (result) {
+                        el.innerHTML = result.svg;
+                        window.__md2pdfPending--;
+                    }).catch(function () {
+                        window.__md2pdfPending--;
+                    });
+                });
+            }
+
+            if (window.Viz) {
+                var viz = new Viz();
+                graphvizEls.forEach(function (el) {
+                    viz.renderSVGElement(el.textContent).then(function (svgEl) {
+                        el.innerHTML = '';
+                        el.appendChild(svgEl);
+                        window.__md2pdfPending--;
+                    }).catch(function () {
+                        window.__md2pdfPending--;
+                    });
+                });
+            }
+
+            if (window.katex) {
+                mathEls.forEach(function (el) {
+                    try {
+                        katex.render(el.textContent, el, { displayMode: true, throwOnError: false });
+                    } catch (e) {
+                        // leave the raw text in place on render failure
+                    }
+                    window.__md2pdfPending--;
+                });
+            } else {
+                window.__md2pdfPending -= mathEls.length;
+            }
+
+            if (window.renderMathInElement) {
+                renderMathInElement(document.body, {
+                    delimiters: [
+                        { left: '$$', right: '$$', display: true },
+                        { left: '\\[', right: '\\]', display: true }
+                    ]
+                });
+            }
+        });
+    </script>
+"#;
+
+/// Builds the `<head>` of the document around the CSS from [`resolve_css`].
+fn get_html_template(theme_config: &ThemeConfig) -> Result<String> {
+    let style_block = resolve_css(theme_config)?;
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <style>
+{style_block}
     </style>
+{RENDERER_SCRIPTS}
 </head>
-"#.to_string()
+"#
+    ))
+}
+
+/// Polls `window.__md2pdfPending` (set by `RENDERER_SCRIPTS`) until every
+/// mermaid/graphviz diagram has finished rendering, or `timeout` elapses.
+async fn wait_for_renderers(tab: &headless_chrome::Tab) -> Result<()> {
+    let timeout = Duration::from_secs(10);
+    let started = std::time::Instant::now();
+    loop {
+        let ready = tab
+            .evaluate("window.__md2pdfPending === 0", false)?
+            .value
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if ready || started.elapsed() >= timeout {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
 }
 
 #[tokio::main]
-async fn generate_pdf(html: &str, output_path: &PathBuf, _margin: u32) -> Result<()> {
+async fn generate_pdf(html: &str, output_path: &PathBuf, layout: PdfLayout) -> Result<()> {
     // Save temporary HTML
     let temp_html = output_path.with_extension("html");
     fs::write(&temp_html, html)?;
-    
+
     // Configure the browser
     let options = LaunchOptions {
         headless: true,
@@ -435,25 +1061,166 @@ async fn generate_pdf(html: &str, output_path: &PathBuf, _margin: u32) -> Result
         enable_gpu: false,
         ..Default::default()
     };
-    
+
     let browser = Browser::new(options)?;
     let tab = browser.new_tab()?;
-    
+
     // Load the HTML
     let temp_html_abs = fs::canonicalize(&temp_html)?;
     let file_url = format!("file:///{}", temp_html_abs.display().to_string().replace(" ", "%20"));
     tab.navigate_to(&file_url)?;
     tab.wait_until_navigated()?;
-    
-    // Wait for content to load
-    tokio::time::sleep(Duration::from_secs(2)).await;
-    
-    // Generate the PDF with default options
-    let pdf_data = tab.print_to_pdf(None)?;
+
+    // Wait for diagram/math renderers to finish instead of a fixed delay
+    wait_for_renderers(&tab).await?;
+
+    let (paper_width, paper_height) = layout.paper.dimensions_in();
+    // Slide decks print landscape with no PDF margin, since the
+    // `.slides > section` CSS already supplies its own padding.
+    let (landscape, margin_in) = if layout.slides {
+        (true, 0.0)
+    } else {
+        (layout.landscape, layout.margin_px as f64 / 96.0)
+    };
+    let display_header_footer =
+        layout.header_template.is_some() || layout.footer_template.is_some();
+
+    let print_options = headless_chrome::types::PrintToPdfOptions {
+        landscape: Some(landscape),
+        print_background: Some(true),
+        paper_width: Some(paper_width),
+        paper_height: Some(paper_height),
+        margin_top: Some(margin_in),
+        margin_bottom: Some(margin_in),
+        margin_left: Some(margin_in),
+        margin_right: Some(margin_in),
+        display_header_footer: Some(display_header_footer),
+        header_template: Some(layout.header_template.unwrap_or_default()),
+        footer_template: Some(layout.footer_template.unwrap_or_default()),
+        ..Default::default()
+    };
+    let pdf_data = tab.print_to_pdf(Some(print_options))?;
     fs::write(output_path, pdf_data)?;
-    
+
     // Remove temporary file
     fs::remove_file(&temp_html)?;
-    
+
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_lowercases_and_dashes_whitespace() {
+        let mut seen = HashMap::new();
+        assert_eq!(slugify("Hello World", &mut seen), "hello-world");
+    }
+
+    #[test]
+    fn slugify_drops_punctuation() {
+        let mut seen = HashMap::new();
+        assert_eq!(slugify("What's New?!", &mut seen), "whats-new");
+    }
+
+    #[test]
+    fn slugify_dedupes_repeated_headings() {
+        let mut seen = HashMap::new();
+        assert_eq!(slugify("Intro", &mut seen), "intro");
+        assert_eq!(slugify("Intro", &mut seen), "intro-1");
+        assert_eq!(slugify("Intro", &mut seen), "intro-2");
+    }
+
+    #[test]
+    fn collect_headings_assigns_levels_and_slugs_in_order() {
+        let markdown = "# Title\n\nSome text\n\n## Sub Heading\n\n### Deep\n";
+        let headings = collect_headings(markdown);
+        assert_eq!(
+            headings,
+            vec![
+                (1, "Title".to_string(), "title".to_string()),
+                (2, "Sub Heading".to_string(), "sub-heading".to_string()),
+                (3, "Deep".to_string(), "deep".to_string()),
+            ]
+        );
+    }
+
+    /// Counts every `<li...>`/`</li>` and `<ul...>`/`</ul>` occurrence so a
+    /// test can assert the generated TOC is balanced without hand-parsing
+    /// the HTML.
+    fn tag_counts(html: &str) -> (usize, usize, usize, usize) {
+        (
+            html.matches("<li>").count(),
+            html.matches("</li>").count(),
+            html.matches("<ul>").count(),
+            html.matches("</ul>").count(),
+        )
+    }
+
+    #[test]
+    fn render_toc_balances_simple_nesting() {
+        let headings = vec![
+            (1, "A".to_string(), "a".to_string()),
+            (2, "B".to_string(), "b".to_string()),
+            (3, "C".to_string(), "c".to_string()),
+        ];
+        let toc = render_toc(&headings);
+        let (li_open, li_close, ul_open, ul_close) = tag_counts(&toc);
+        assert_eq!(li_open, li_close);
+        assert_eq!(ul_open, ul_close);
+        assert_eq!(li_open, 3);
+    }
+
+    #[test]
+    fn render_toc_balances_when_levels_are_skipped() {
+        // H1 -> H3 -> H2: C nests under A in its own list, as a sibling of
+        // B's list, since B (H3) and C (H2) can't share a <ul>.
+        let headings = vec![
+            (1, "A".to_string(), "a".to_string()),
+            (3, "B".to_string(), "b".to_string()),
+            (2, "C".to_string(), "c".to_string()),
+        ];
+        let toc = render_toc(&headings);
+        let (li_open, li_close, ul_open, ul_close) = tag_counts(&toc);
+        assert_eq!(li_open, li_close);
+        assert_eq!(ul_open, ul_close);
+        assert_eq!(li_open, 3);
+    }
+
+    #[test]
+    fn render_toc_balances_deep_skip_back_to_a_sibling() {
+        // H1 -> H2 -> H4 -> H1: D is a sibling of A at the top level.
+        let headings = vec![
+            (1, "A".to_string(), "a".to_string()),
+            (2, "B".to_string(), "b".to_string()),
+            (4, "C".to_string(), "c".to_string()),
+            (1, "D".to_string(), "d".to_string()),
+        ];
+        let toc = render_toc(&headings);
+        let (li_open, li_close, ul_open, ul_close) = tag_counts(&toc);
+        assert_eq!(li_open, li_close);
+        assert_eq!(ul_open, ul_close);
+        assert_eq!(li_open, 4);
+    }
+
+    #[test]
+    fn render_toc_clamps_heading_shallower_than_the_first() {
+        // Document starts with H2, then drops to H1: both become top-level
+        // siblings instead of leaving a dangling close tag.
+        let headings = vec![
+            (2, "A".to_string(), "a".to_string()),
+            (1, "B".to_string(), "b".to_string()),
+        ];
+        let toc = render_toc(&headings);
+        let (li_open, li_close, ul_open, ul_close) = tag_counts(&toc);
+        assert_eq!(li_open, li_close);
+        assert_eq!(ul_open, ul_close);
+        assert_eq!(ul_open, 1);
+    }
+
+    #[test]
+    fn render_toc_empty_headings_is_empty_string() {
+        assert_eq!(render_toc(&[]), "");
+    }
 }
\ No newline at end of file